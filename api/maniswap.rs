@@ -1,7 +1,17 @@
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use vercel_runtime::{run, Body, Error, Request, Response, StatusCode};
 use std::f64;
 
+fn default_p() -> f64 {
+    0.5
+}
+
+fn default_fee_bps() -> u32 {
+    100
+}
+
 #[derive(Deserialize, Serialize)]
 struct SwapRequest {
     token_a: String,
@@ -10,13 +20,167 @@ struct SwapRequest {
     reserve_b: f64,
     input_token: String,
     amount_in: f64,
+    #[serde(default = "default_p")]
+    p: f64,
+    #[serde(default = "default_fee_bps")]
+    fee_bps: u32,
+    min_amount_out: Option<f64>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 struct SwapResponse {
+    gross_amount_out: f64,
+    amount_out: f64,
+    fee_amount: f64,
+    new_reserve_a: f64,
+    new_reserve_b: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct QuoteResponse {
     amount_out: f64,
+    price_before: f64,
+    price_after: f64,
+    price_impact: f64,
+}
+
+#[derive(Deserialize)]
+struct GetReservesParams {
+    reserve_a: f64,
+    reserve_b: f64,
+    #[serde(default = "default_p")]
+    p: f64,
+}
+
+#[derive(Serialize)]
+struct GetReservesResponse {
+    reserve_a: f64,
+    reserve_b: f64,
+    implied_probability: f64,
+}
+
+#[derive(Deserialize)]
+struct AddLiquidityParams {
+    reserve_a: f64,
+    reserve_b: f64,
+    amount_a: f64,
+    amount_b: f64,
+    total_shares: f64,
+}
+
+#[derive(Serialize)]
+struct AddLiquidityResponse {
+    shares_minted: f64,
+    amount_a_used: f64,
+    amount_b_used: f64,
+    new_reserve_a: f64,
+    new_reserve_b: f64,
+    rebalanced: bool,
+}
+
+#[derive(Deserialize)]
+struct RemoveLiquidityParams {
+    reserve_a: f64,
+    reserve_b: f64,
+    total_shares: f64,
+    shares_to_burn: f64,
+}
+
+#[derive(Serialize)]
+struct RemoveLiquidityResponse {
+    amount_a: f64,
+    amount_b: f64,
+    new_reserve_a: f64,
+    new_reserve_b: f64,
+    new_total_shares: f64,
+}
+
+#[derive(Deserialize)]
+struct RebalanceParams {
+    token_a: String,
+    reserve_a: f64,
+    token_b: String,
+    reserve_b: f64,
+    #[serde(default = "default_p")]
+    p: f64,
+    reference_price: f64,
+    max_rebalance: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RebalanceResponse {
+    input_token: String,
+    amount_in: f64,
     new_reserve_a: f64,
     new_reserve_b: f64,
+    resulting_probability: f64,
+}
+
+/// A JSON-RPC 2.0 application error. Codes in `-32000..=-32099` are reserved
+/// for server-defined errors (invariant/validation failures, slippage);
+/// the rest follow the standard JSON-RPC reserved ranges.
+#[derive(Debug)]
+struct ApiError {
+    code: i32,
+    message: String,
+    data: Option<Value>,
+}
+
+impl ApiError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    fn with_data(code: i32, message: impl Into<String>, data: Value) -> Self {
+        ApiError {
+            code,
+            message: message.into(),
+            data: Some(data),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RpcErrorBody {
+    code: i32,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcErrorBody>,
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    #[allow(dead_code)]
+    jsonrpc: String,
+    method: Method,
+    params: Value,
+    #[serde(default)]
+    id: Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Method {
+    Swap,
+    Quote,
+    AddLiquidity,
+    RemoveLiquidity,
+    GetReserves,
+    Rebalance,
 }
 
 #[tokio::main]
@@ -25,7 +189,7 @@ async fn main() -> Result<(), Error> {
 }
 
 pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
-    let swap_request: SwapRequest = match serde_json::from_slice(req.body()) {
+    let rpc_request: RpcRequest = match serde_json::from_slice(req.body()) {
         Ok(data) => data,
         Err(_) => {
             return Ok(Response::builder()
@@ -34,55 +198,463 @@ pub async fn handler(req: Request) -> Result<Response<Body>, Error> {
         }
     };
 
-    let (amount_out, new_reserve_a, new_reserve_b) = if swap_request.input_token == swap_request.token_a {
-        // Token A is being swapped for Token B
-        let amount_out = maniswap_swap(
-            swap_request.reserve_a,
-            swap_request.reserve_b,
-            swap_request.amount_in,
-        );
-        (
-            amount_out,
-            swap_request.reserve_a + swap_request.amount_in,
-            swap_request.reserve_b - amount_out,
-        )
+    let id = rpc_request.id.clone();
+    let outcome = match rpc_request.method {
+        Method::Swap => rpc_swap(rpc_request.params, false),
+        Method::Quote => rpc_swap(rpc_request.params, true),
+        Method::AddLiquidity => rpc_add_liquidity(rpc_request.params),
+        Method::RemoveLiquidity => rpc_remove_liquidity(rpc_request.params),
+        Method::GetReserves => rpc_get_reserves(rpc_request.params),
+        Method::Rebalance => rpc_rebalance(rpc_request.params),
+    };
+
+    let response = match outcome {
+        Ok(result) => RpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: Some(result),
+            error: None,
+        },
+        Err(err) => RpcResponse {
+            jsonrpc: "2.0",
+            id,
+            result: None,
+            error: Some(RpcErrorBody {
+                code: err.code,
+                message: err.message,
+                data: err.data,
+            }),
+        },
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&response)?.into())?)
+}
+
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, ApiError> {
+    serde_json::from_value(params)
+        .map_err(|e| ApiError::new(-32602, format!("Invalid params: {}", e)))
+}
+
+/// Shared implementation for the `swap` and `quote` methods: `as_quote` skips the
+/// slippage check and reports price impact instead of the post-trade reserves.
+fn rpc_swap(params: Value, as_quote: bool) -> Result<Value, ApiError> {
+    let swap_request: SwapRequest = parse_params(params)?;
+
+    if swap_request.fee_bps > 10_000 {
+        return Err(ApiError::new(-32602, "fee_bps must be between 0 and 10000"));
+    }
+
+    let (reserve_in, reserve_out, is_a_to_b) = if swap_request.input_token == swap_request.token_a {
+        (swap_request.reserve_a, swap_request.reserve_b, true)
     } else if swap_request.input_token == swap_request.token_b {
-        // Token B is being swapped for Token A
-        let amount_out = maniswap_swap(
-            swap_request.reserve_b,
-            swap_request.reserve_a,
-            swap_request.amount_in,
-        );
-        (
+        (swap_request.reserve_b, swap_request.reserve_a, false)
+    } else {
+        return Err(ApiError::new(-32602, "Invalid input token"));
+    };
+
+    let fee_amount = swap_request.amount_in * (swap_request.fee_bps as f64) / 10_000.0;
+    let net_amount_in = swap_request.amount_in - fee_amount;
+
+    // `implied_probability` weights reserve_b by `p` and reserve_a by `1-p`, and
+    // `maniswap_swap` applies its `p` argument to `reserve_out`. Matching that
+    // convention when `reserve_out` is `a` instead of `b` means swapping the
+    // exponent, or the invariant is preserved in one direction and broken in
+    // the other for any `p != 0.5`.
+    let weight = if is_a_to_b { swap_request.p } else { 1.0 - swap_request.p };
+
+    let gross_amount_out = maniswap_swap(reserve_in, reserve_out, swap_request.amount_in, weight)
+        .map_err(|msg| ApiError::new(-32000, msg))?;
+    let amount_out = maniswap_swap(reserve_in, reserve_out, net_amount_in, weight)
+        .map_err(|msg| ApiError::new(-32000, msg))?;
+
+    if as_quote {
+        // Marginal rate d(amount_out)/d(amount_in) at the given reserves: the
+        // deposit itself passes through 1:1 (see `maniswap_swap`'s `reserve_out
+        // + amount_in - new_out`), plus the curve-derived redemption term.
+        let spot_price = |reserve_in: f64, reserve_out: f64| -> f64 {
+            1.0 + (1.0 - weight) / weight * (reserve_out / reserve_in)
+        };
+        let price_before = spot_price(reserve_in, reserve_out);
+        let price_after = spot_price(reserve_in + net_amount_in, reserve_out + net_amount_in - amount_out);
+        let avg_execution_price = amount_out / swap_request.amount_in;
+        let price_impact = (price_before - avg_execution_price) / price_before * 100.0;
+
+        let response = QuoteResponse {
             amount_out,
-            swap_request.reserve_a - amount_out,
-            swap_request.reserve_b + swap_request.amount_in,
+            price_before,
+            price_after,
+            price_impact,
+        };
+        return Ok(serde_json::to_value(response).unwrap());
+    }
+
+    if let Some(min_amount_out) = swap_request.min_amount_out {
+        if amount_out < min_amount_out {
+            return Err(ApiError::with_data(
+                -32001,
+                "slippage",
+                serde_json::json!({
+                    "error": "slippage",
+                    "amount_out": amount_out,
+                    "min_amount_out": min_amount_out,
+                }),
+            ));
+        }
+    }
+
+    let (new_reserve_a, new_reserve_b) = if is_a_to_b {
+        (
+            swap_request.reserve_a + net_amount_in,
+            swap_request.reserve_b + net_amount_in - amount_out,
         )
     } else {
-        return Ok(Response::builder()
-            .status(StatusCode::BAD_REQUEST)
-            .body("Invalid input token".into())?)
+        (
+            swap_request.reserve_a + net_amount_in - amount_out,
+            swap_request.reserve_b + net_amount_in,
+        )
     };
 
     let response = SwapResponse {
+        gross_amount_out,
         amount_out,
+        fee_amount,
         new_reserve_a,
         new_reserve_b,
     };
 
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_string(&response)?.into())?)
+    Ok(serde_json::to_value(response).unwrap())
+}
+
+fn rpc_get_reserves(params: Value) -> Result<Value, ApiError> {
+    let params: GetReservesParams = parse_params(params)?;
+
+    if params.p <= 0.0 || params.p >= 1.0 {
+        return Err(ApiError::new(-32602, "p must be strictly between 0 and 1"));
+    }
+    if params.reserve_a <= 0.0 || params.reserve_b <= 0.0 {
+        return Err(ApiError::new(-32602, "reserves must be positive"));
+    }
+
+    let implied_probability = implied_probability(params.reserve_a, params.reserve_b, params.p);
+
+    Ok(serde_json::to_value(GetReservesResponse {
+        reserve_a: params.reserve_a,
+        reserve_b: params.reserve_b,
+        implied_probability,
+    })
+    .unwrap())
 }
 
-fn maniswap_swap(reserve_in: f64, reserve_out: f64, amount_in: f64) -> f64 {
-    // Maniswap protocol formula: y^p * n^(1-p) = k
-    // Assume p = 0.5 for simplicity, but this can be adjusted.
-    let p = 0.5;
-    let y = reserve_out;
-    let n = reserve_in + amount_in;
-    let k = (y.powf(p)) * (n.powf(1.0 - p));
-    let new_y = k / n.powf(1.0 - p);
-    y - new_y
-}
\ No newline at end of file
+fn rpc_add_liquidity(params: Value) -> Result<Value, ApiError> {
+    let params: AddLiquidityParams = parse_params(params)?;
+
+    if params.amount_a <= 0.0 || params.amount_b <= 0.0 {
+        return Err(ApiError::new(-32602, "amount_a and amount_b must be positive"));
+    }
+    if params.reserve_a < 0.0 || params.reserve_b < 0.0 || params.total_shares < 0.0 {
+        return Err(ApiError::new(-32602, "reserves and total_shares must be non-negative"));
+    }
+
+    // First deposit bootstraps the pool at whatever ratio is given; subsequent
+    // deposits are balanced to the existing reserve ratio so the deposit can't
+    // move the p-weighted price. The unmatched half of an imbalanced deposit
+    // is simply not used (the caller is expected to refund it).
+    let (amount_a_used, amount_b_used, rebalanced, shares_minted) = if params.total_shares <= 0.0 {
+        (
+            params.amount_a,
+            params.amount_b,
+            false,
+            (params.amount_a * params.amount_b).sqrt(),
+        )
+    } else if params.reserve_a > 0.0 && params.reserve_b > 0.0 {
+        let optimal_b = params.amount_a * params.reserve_b / params.reserve_a;
+        let (amount_a_used, amount_b_used) = if optimal_b <= params.amount_b {
+            (params.amount_a, optimal_b)
+        } else {
+            (params.amount_b * params.reserve_a / params.reserve_b, params.amount_b)
+        };
+        let rebalanced = amount_a_used != params.amount_a || amount_b_used != params.amount_b;
+        let shares_minted = params.total_shares * amount_a_used / params.reserve_a;
+        (amount_a_used, amount_b_used, rebalanced, shares_minted)
+    } else {
+        return Err(ApiError::new(
+            -32000,
+            "reserves are zero but total_shares is outstanding",
+        ));
+    };
+
+    Ok(serde_json::to_value(AddLiquidityResponse {
+        shares_minted,
+        amount_a_used,
+        amount_b_used,
+        new_reserve_a: params.reserve_a + amount_a_used,
+        new_reserve_b: params.reserve_b + amount_b_used,
+        rebalanced,
+    })
+    .unwrap())
+}
+
+fn rpc_remove_liquidity(params: Value) -> Result<Value, ApiError> {
+    let params: RemoveLiquidityParams = parse_params(params)?;
+
+    if params.total_shares <= 0.0 {
+        return Err(ApiError::new(-32602, "total_shares must be positive"));
+    }
+    if params.shares_to_burn <= 0.0 || params.shares_to_burn > params.total_shares {
+        return Err(ApiError::new(
+            -32602,
+            "shares_to_burn must be positive and no greater than total_shares",
+        ));
+    }
+
+    let share_of_pool = params.shares_to_burn / params.total_shares;
+    let amount_a = params.reserve_a * share_of_pool;
+    let amount_b = params.reserve_b * share_of_pool;
+
+    Ok(serde_json::to_value(RemoveLiquidityResponse {
+        amount_a,
+        amount_b,
+        new_reserve_a: params.reserve_a - amount_a,
+        new_reserve_b: params.reserve_b - amount_b,
+        new_total_shares: params.total_shares - params.shares_to_burn,
+    })
+    .unwrap())
+}
+
+fn rpc_rebalance(params: Value) -> Result<Value, ApiError> {
+    let params: RebalanceParams = parse_params(params)?;
+
+    if params.p <= 0.0 || params.p >= 1.0 {
+        return Err(ApiError::new(-32602, "p must be strictly between 0 and 1"));
+    }
+    if params.reserve_a <= 0.0 || params.reserve_b <= 0.0 {
+        return Err(ApiError::new(-32602, "reserves must be positive"));
+    }
+    if params.reference_price <= 0.0 || params.reference_price >= 1.0 {
+        return Err(ApiError::new(-32602, "reference_price must be strictly between 0 and 1"));
+    }
+    if params.max_rebalance < 0.0 {
+        return Err(ApiError::new(-32602, "max_rebalance must be non-negative"));
+    }
+
+    let p = params.p;
+    let t = params.reference_price;
+    let current_probability = implied_probability(params.reserve_a, params.reserve_b, p);
+
+    // Buying a (paying in b) raises the implied probability of a (and vice versa), so the
+    // direction of the arbitrage trade follows the sign of the price gap.
+    let (input_token, amount_in, new_reserve_a, new_reserve_b) = if t >= current_probability {
+        // `implied_probability` weights reserve_b by `p`; this branch solves for
+        // reserve_b, so the invariant and the maniswap_swap call (reserve_out = a)
+        // both need the complementary exponent, mirroring the `else` branch below.
+        let k = params.reserve_a.powf(1.0 - p) * params.reserve_b.powf(p);
+        let target_reserve_b = k * (t * (1.0 - p) / (p * (1.0 - t))).powf(1.0 - p);
+        let desired_amount_in = (target_reserve_b - params.reserve_b).max(0.0);
+        let amount_in = desired_amount_in.min(params.max_rebalance);
+        let amount_out = maniswap_swap(params.reserve_b, params.reserve_a, amount_in, 1.0 - p)
+            .map_err(|msg| ApiError::new(-32000, msg))?;
+        (
+            params.token_b.clone(),
+            amount_in,
+            params.reserve_a + amount_in - amount_out,
+            params.reserve_b + amount_in,
+        )
+    } else {
+        let k = params.reserve_b.powf(p) * params.reserve_a.powf(1.0 - p);
+        let target_reserve_a = k * (p * (1.0 - t) / (t * (1.0 - p))).powf(p);
+        let desired_amount_in = (target_reserve_a - params.reserve_a).max(0.0);
+        let amount_in = desired_amount_in.min(params.max_rebalance);
+        let amount_out = maniswap_swap(params.reserve_a, params.reserve_b, amount_in, p)
+            .map_err(|msg| ApiError::new(-32000, msg))?;
+        (
+            params.token_a.clone(),
+            amount_in,
+            params.reserve_a + amount_in,
+            params.reserve_b + amount_in - amount_out,
+        )
+    };
+
+    let resulting_probability = implied_probability(new_reserve_a, new_reserve_b, p);
+
+    Ok(serde_json::to_value(RebalanceResponse {
+        input_token,
+        amount_in,
+        new_reserve_a,
+        new_reserve_b,
+        resulting_probability,
+    })
+    .unwrap())
+}
+
+/// The Maniswap invariant's implied probability of the `a` side,
+/// `p`-weighted so it reduces to `reserve_b / (reserve_a + reserve_b)` at `p = 0.5`.
+fn implied_probability(reserve_a: f64, reserve_b: f64, p: f64) -> f64 {
+    p * reserve_b / (p * reserve_b + (1.0 - p) * reserve_a)
+}
+
+// Maniswap protocol formula: y^p * n^(1-p) = k, where `y` is the reserve of the
+// token being bought and `n` is the reserve of the token being sold in.
+// A buy deposits `amount_in` into both pools, then redeems the bought token out
+// of the output pool, so the post-trade input reserve is `reserve_in + amount_in`
+// and the output reserve is solved for the value that restores the pre-trade `k`.
+fn maniswap_swap(reserve_in: f64, reserve_out: f64, amount_in: f64, p: f64) -> Result<f64, &'static str> {
+    if p <= 0.0 || p >= 1.0 {
+        return Err("p must be strictly between 0 and 1");
+    }
+    if reserve_in <= 0.0 || reserve_out <= 0.0 {
+        return Err("reserves must be positive");
+    }
+    if amount_in < 0.0 {
+        return Err("amount_in must be non-negative");
+    }
+
+    let k = reserve_out.powf(p) * reserve_in.powf(1.0 - p);
+    let new_reserve_in = reserve_in + amount_in;
+    let new_reserve_out = (k / new_reserve_in.powf(1.0 - p)).powf(1.0 / p);
+
+    if !new_reserve_out.is_finite() || new_reserve_out <= 0.0 {
+        return Err("amount_in is too large for the current reserves");
+    }
+
+    Ok(reserve_out + amount_in - new_reserve_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invariant_preserved_across_a_swap() {
+        let reserve_in = 100.0;
+        let reserve_out = 100.0;
+        let p = 0.5;
+        let amount_in = 10.0;
+        let amount_out = maniswap_swap(reserve_in, reserve_out, amount_in, p).unwrap();
+        let new_reserve_in = reserve_in + amount_in;
+        let new_reserve_out = reserve_out + amount_in - amount_out;
+        let k_before = reserve_out.powf(p) * reserve_in.powf(1.0 - p);
+        let k_after = new_reserve_out.powf(p) * new_reserve_in.powf(1.0 - p);
+        assert!((k_before - k_after).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invariant_preserved_for_asymmetric_p() {
+        let reserve_in = 300.0;
+        let reserve_out = 120.0;
+        let p = 0.3;
+        let amount_in = 25.0;
+        let amount_out = maniswap_swap(reserve_in, reserve_out, amount_in, p).unwrap();
+        let new_reserve_in = reserve_in + amount_in;
+        let new_reserve_out = reserve_out + amount_in - amount_out;
+        let k_before = reserve_out.powf(p) * reserve_in.powf(1.0 - p);
+        let k_after = new_reserve_out.powf(p) * new_reserve_in.powf(1.0 - p);
+        assert!((k_before - k_after).abs() < 1e-6);
+    }
+
+    #[test]
+    fn amount_out_is_monotonic_in_amount_in() {
+        let small = maniswap_swap(100.0, 100.0, 1.0, 0.5).unwrap();
+        let large = maniswap_swap(100.0, 100.0, 50.0, 0.5).unwrap();
+        assert!(large > small);
+    }
+
+    #[test]
+    fn rejects_invalid_p() {
+        assert!(maniswap_swap(100.0, 100.0, 10.0, 0.0).is_err());
+        assert!(maniswap_swap(100.0, 100.0, 10.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn rejects_non_positive_reserves() {
+        assert!(maniswap_swap(0.0, 100.0, 10.0, 0.5).is_err());
+        assert!(maniswap_swap(100.0, 0.0, 10.0, 0.5).is_err());
+    }
+
+    #[test]
+    fn fee_is_deducted_from_effective_input() {
+        let params = serde_json::json!({
+            "token_a": "YES", "reserve_a": 100.0, "token_b": "NO", "reserve_b": 100.0,
+            "input_token": "YES", "amount_in": 10.0, "fee_bps": 100
+        });
+        let result = rpc_swap(params, false).unwrap();
+        let response: SwapResponse = serde_json::from_value(result).unwrap();
+        assert_eq!(response.fee_amount, 0.1);
+        assert!(response.amount_out < response.gross_amount_out);
+    }
+
+    #[test]
+    fn rejects_fee_bps_out_of_range() {
+        let params = serde_json::json!({
+            "token_a": "YES", "reserve_a": 100.0, "token_b": "NO", "reserve_b": 100.0,
+            "input_token": "YES", "amount_in": 10.0, "fee_bps": 10_001
+        });
+        let err = rpc_swap(params, false).unwrap_err();
+        assert_eq!(err.code, -32602);
+    }
+
+    #[test]
+    fn slippage_guard_rejects_below_min_amount_out() {
+        let params = serde_json::json!({
+            "token_a": "YES", "reserve_a": 100.0, "token_b": "NO", "reserve_b": 100.0,
+            "input_token": "YES", "amount_in": 10.0, "min_amount_out": 999.0
+        });
+        let err = rpc_swap(params, false).unwrap_err();
+        assert_eq!(err.code, -32001);
+    }
+
+    #[test]
+    fn quote_execution_price_is_bounded_by_before_and_after() {
+        let params = serde_json::json!({
+            "token_a": "YES", "reserve_a": 100.0, "token_b": "NO", "reserve_b": 100.0,
+            "input_token": "YES", "amount_in": 10.0, "fee_bps": 100
+        });
+        let result = rpc_swap(params, true).unwrap();
+        let quote: QuoteResponse = serde_json::from_value(result).unwrap();
+        let avg_execution_price = quote.amount_out / 10.0;
+        assert!(quote.price_after <= avg_execution_price);
+        assert!(avg_execution_price <= quote.price_before);
+    }
+
+    #[test]
+    fn invariant_preserved_through_rpc_swap_for_both_directions_with_asymmetric_p() {
+        let p = 0.3;
+        let k_before = 100f64.powf(1.0 - p) * 200f64.powf(p);
+
+        let params = serde_json::json!({
+            "token_a": "YES", "reserve_a": 100.0, "token_b": "NO", "reserve_b": 200.0,
+            "input_token": "YES", "amount_in": 10.0, "p": p
+        });
+        let result = rpc_swap(params, false).unwrap();
+        let response: SwapResponse = serde_json::from_value(result).unwrap();
+        let k_after = response.new_reserve_a.powf(1.0 - p) * response.new_reserve_b.powf(p);
+        assert!((k_before - k_after).abs() < 1e-6);
+
+        let params = serde_json::json!({
+            "token_a": "YES", "reserve_a": 100.0, "token_b": "NO", "reserve_b": 200.0,
+            "input_token": "NO", "amount_in": 10.0, "p": p
+        });
+        let result = rpc_swap(params, false).unwrap();
+        let response: SwapResponse = serde_json::from_value(result).unwrap();
+        let k_after = response.new_reserve_a.powf(1.0 - p) * response.new_reserve_b.powf(p);
+        assert!((k_before - k_after).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rebalance_hits_target_probability_when_raising_with_asymmetric_p() {
+        let p = 0.3;
+        let params = serde_json::json!({
+            "token_a": "YES", "reserve_a": 100.0, "token_b": "NO", "reserve_b": 50.0,
+            "p": p, "reference_price": 0.6, "max_rebalance": 1_000_000.0
+        });
+        let result = rpc_rebalance(params).unwrap();
+        let response: RebalanceResponse = serde_json::from_value(result).unwrap();
+        assert!((response.resulting_probability - 0.6).abs() < 1e-6);
+
+        let k_before = 100f64.powf(1.0 - p) * 50f64.powf(p);
+        let k_after = response.new_reserve_a.powf(1.0 - p) * response.new_reserve_b.powf(p);
+        assert!((k_before - k_after).abs() < 1e-6);
+    }
+}